@@ -23,7 +23,10 @@
 use super::{
 	diagnostics::cpu::check_protection_status,
 	gdt::{GDTDescriptor, Gate},
+	multiboot::MultibootInfo,
 };
+use crate::sync::Locked;
+use core::{cell::OnceCell, str};
 
 const PHYSICAL_GDT_ADDRESS: u32 = 0x00000800;
 extern "C" {
@@ -32,24 +35,33 @@ extern "C" {
 }
 
 #[doc(hidden)]
-pub type GdtGates = [Gate; 5];
+pub type GdtGates = [Gate; 7];
 
 /// Global Descriptor Table (GDT) entries that define memory segments and
 /// privilege levels. Each entry consists of a base address, size limit, and
 /// access permissions.
 ///
-/// The 5 entries are:
+/// The 7 entries are:
 /// - [0] Null Descriptor: Required by CPU, must be zero
 /// - [1] Kernel Code (Ring 0): Executable segment for kernel code
 /// - [2] Kernel Data (Ring 0): Read/write segment for kernel data
 /// - [3] User Code (Ring 3): Executable segment for user programs
 /// - [4] User Data (Ring 3): Read/write segment for user data
+/// - [5] Kernel TSS: hosts `KERNEL_TSS`, loaded into the task register
+/// - [6] Double-Fault TSS: hosts `DOUBLE_FAULT_TSS`, reached via a task gate
 ///
 /// Access bytes control permissions:
 /// - 0b10011010: Ring 0 code (kernel, executable)
 /// - 0b10010010: Ring 0 data (kernel, writable)
 /// - 0b11111010: Ring 3 code (user, executable)
 /// - 0b11110010: Ring 3 data (user, writable)
+///
+/// Entries [5] and [6] are placeholders at link time: a 32-bit TSS descriptor
+/// embeds the TSS's base address, which isn't known until the statics below
+/// have a concrete link address, so `gdt_init` patches them in before the GDT
+/// is flushed. Unlike on x86_64, a 32-bit TSS descriptor is a normal 8-byte
+/// system segment, so it fits directly in this `Gate` array without widening
+/// the format.
 #[no_mangle]
 #[link_section = ".gdt"]
 pub static GDT_ENTRIES: GdtGates = [
@@ -59,18 +71,209 @@ pub static GDT_ENTRIES: GdtGates = [
 	Gate::new(0, !0, 0b10010010, 0b1100), // [2] Kernel Data: Ring 0, writable
 	Gate::new(0, !0, 0b11111010, 0b1100), // [3] User Code: Ring 3, executable
 	Gate::new(0, !0, 0b11110010, 0b1100), // [4] User Data: Ring 3, writable
+	Gate(0), // [5] Kernel TSS: patched in `gdt_init`
+	Gate(0), // [6] Double-Fault TSS: patched in `gdt_init`
 ];
 
-// Future expansion:
-// - TSS (Task State Segment) entries will be needed for task switching
-// gdt::Gate(0),  // TSS 1
-// gdt::Gate(0),  // TSS 2
+/// Selector for the kernel's main TSS (GDT index 5, ring 0).
+const KERNEL_TSS_SELECTOR: u16 = 5 * 8;
+
+/// Selector for the dedicated double-fault TSS (GDT index 6, ring 0).
+///
+/// Consumed by the IDT setup to build vector 8's task gate.
+pub const DOUBLE_FAULT_TSS_SELECTOR: u16 = 6 * 8;
+
+/// Selector for kernel code, as laid out in [`GDT_ENTRIES`].
+const KERNEL_CODE_SELECTOR: u16 = 1 * 8;
+
+/// Selector for kernel data, as laid out in [`GDT_ENTRIES`].
+const KERNEL_DATA_SELECTOR: u16 = 2 * 8;
+
+/// Access byte for a present, ring-0, 32-bit available TSS descriptor.
+const TSS_ACCESS_BYTE: u8 = 0b1000_1001;
+
+/// Size, in bytes, of the stack reserved for the double-fault handler. Only
+/// needs to survive a double fault, not host general execution.
+const DOUBLE_FAULT_STACK_SIZE: usize = 4096 * 4;
+
+/// x86 32-bit Task State Segment.
+///
+/// This kernel does not use the TSS for hardware task switching between
+/// ordinary tasks; `KERNEL_TSS` is the TSS held in the task register (only
+/// its SS0 is set; ESP0 is left zero), and `DOUBLE_FAULT_TSS` exists so a
+/// double fault (including
+/// one caused by kernel stack overflow) can hardware-task-switch onto a known
+/// good stack instead of triple-faulting the machine.
+#[repr(C, packed)]
+struct TaskStateSegment {
+	prev_task_link: u16,
+	reserved_0: u16,
+	esp0: u32,
+	ss0: u16,
+	reserved_1: u16,
+	esp1: u32,
+	ss1: u16,
+	reserved_2: u16,
+	esp2: u32,
+	ss2: u16,
+	reserved_3: u16,
+	cr3: u32,
+	eip: u32,
+	eflags: u32,
+	eax: u32,
+	ecx: u32,
+	edx: u32,
+	ebx: u32,
+	esp: u32,
+	ebp: u32,
+	esi: u32,
+	edi: u32,
+	es: u16,
+	reserved_4: u16,
+	cs: u16,
+	reserved_5: u16,
+	ss: u16,
+	reserved_6: u16,
+	ds: u16,
+	reserved_7: u16,
+	fs: u16,
+	reserved_8: u16,
+	gs: u16,
+	reserved_9: u16,
+	ldt_selector: u16,
+	reserved_10: u16,
+	trap: u16,
+	iomap_base: u16,
+}
+
+impl TaskStateSegment {
+	const fn empty() -> Self {
+		TaskStateSegment {
+			prev_task_link: 0,
+			reserved_0: 0,
+			esp0: 0,
+			ss0: 0,
+			reserved_1: 0,
+			esp1: 0,
+			ss1: 0,
+			reserved_2: 0,
+			esp2: 0,
+			ss2: 0,
+			reserved_3: 0,
+			cr3: 0,
+			eip: 0,
+			eflags: 0,
+			eax: 0,
+			ecx: 0,
+			edx: 0,
+			ebx: 0,
+			esp: 0,
+			ebp: 0,
+			esi: 0,
+			edi: 0,
+			es: 0,
+			reserved_4: 0,
+			cs: 0,
+			reserved_5: 0,
+			ss: 0,
+			reserved_6: 0,
+			ds: 0,
+			reserved_7: 0,
+			fs: 0,
+			reserved_8: 0,
+			gs: 0,
+			reserved_9: 0,
+			ldt_selector: 0,
+			reserved_10: 0,
+			trap: 0,
+			iomap_base: 0,
+		}
+	}
+}
+
+/// TSS loaded into the task register by `gdt_init`. The CPU saves the
+/// interrupted context here when a double fault task-switches away. Only SS0
+/// is set (to the kernel data selector); ESP0 is left zero.
+static mut KERNEL_TSS: TaskStateSegment = TaskStateSegment::empty();
+
+/// Dedicated fault-handling stack, switched to via a hardware task switch
+/// whenever a double fault occurs, so a double fault caused by kernel stack
+/// overflow still lands on known-good memory.
+static mut DOUBLE_FAULT_STACK: [u8; DOUBLE_FAULT_STACK_SIZE] =
+	[0; DOUBLE_FAULT_STACK_SIZE];
+
+/// TSS describing the context the CPU switches into on a double fault.
+/// `idt_init` configures IDT vector 8 as a task gate selecting
+/// [`DOUBLE_FAULT_TSS_SELECTOR`] so that this is what actually takes effect
+/// when a double fault occurs.
+static mut DOUBLE_FAULT_TSS: TaskStateSegment = TaskStateSegment::empty();
+
+/// Writes a TSS descriptor for GDT index `index` directly into the
+/// physically-addressed GDT, since [`GDT_ENTRIES`] is immutable `static`
+/// data and the real base address of each TSS is only known once its static
+/// has a concrete link address.
+///
+/// # Safety
+/// Must run before `gdt_flush`, and `index` must point at an entry reserved
+/// for a TSS placeholder.
+unsafe fn install_tss_descriptor(index: usize, base: u32, limit: u32) {
+	let descriptor = Gate::new(base, limit, TSS_ACCESS_BYTE, 0b0000);
+
+	unsafe {
+		let entry = (PHYSICAL_GDT_ADDRESS as *mut Gate).add(index);
+		core::ptr::write_volatile(entry, descriptor);
+	}
+}
+
+/// Entry point reached via a hardware task switch when a double fault
+/// occurs. By the time this runs the CPU has already loaded
+/// `DOUBLE_FAULT_TSS`'s context, so execution continues on the dedicated
+/// fault stack even if the regular kernel stack overflowed.
+extern "C" fn double_fault_handler() -> ! {
+	panic!("Double fault (possible kernel stack overflow)");
+}
 
 #[no_mangle]
 #[doc(hidden)]
 pub fn gdt_init() {
 	use core::mem::size_of;
 
+	// SAFETY: runs once, before any other core is started and before the TSS
+	// descriptors below are installed, so nothing else can observe or race
+	// these writes.
+	unsafe {
+		let kernel_tss = core::ptr::addr_of_mut!(KERNEL_TSS);
+		(*kernel_tss).ss0 = KERNEL_DATA_SELECTOR;
+
+		let fault_tss = core::ptr::addr_of_mut!(DOUBLE_FAULT_TSS);
+		let stack_top = core::ptr::addr_of_mut!(DOUBLE_FAULT_STACK) as u32
+			+ DOUBLE_FAULT_STACK_SIZE as u32;
+		let mut cr3: u32;
+		core::arch::asm!("mov {0}, cr3", out(reg) cr3, options(nomem, nostack, preserves_flags));
+
+		(*fault_tss).cr3 = cr3;
+		(*fault_tss).esp = stack_top;
+		(*fault_tss).ss = KERNEL_DATA_SELECTOR;
+		(*fault_tss).cs = KERNEL_CODE_SELECTOR;
+		(*fault_tss).ds = KERNEL_DATA_SELECTOR;
+		(*fault_tss).es = KERNEL_DATA_SELECTOR;
+		(*fault_tss).fs = KERNEL_DATA_SELECTOR;
+		(*fault_tss).gs = KERNEL_DATA_SELECTOR;
+		(*fault_tss).eip = double_fault_handler as u32;
+		(*fault_tss).eflags = 0x2;
+
+		install_tss_descriptor(
+			5,
+			core::ptr::addr_of!(KERNEL_TSS) as u32,
+			(size_of::<TaskStateSegment>() - 1) as u32,
+		);
+		install_tss_descriptor(
+			6,
+			core::ptr::addr_of!(DOUBLE_FAULT_TSS) as u32,
+			(size_of::<TaskStateSegment>() - 1) as u32,
+		);
+	}
+
 	let gdt_descriptor = GDTDescriptor {
 		size: (size_of::<GdtGates>() - 1) as u16,
 		offset: PHYSICAL_GDT_ADDRESS,
@@ -78,7 +281,179 @@ pub fn gdt_init() {
 
 	unsafe {
 		gdt_flush(&gdt_descriptor as *const _);
+		core::arch::asm!("ltr {0:x}", in(reg) KERNEL_TSS_SELECTOR, options(nostack, preserves_flags));
 	}
 
+	idt_init();
+
 	check_protection_status();
 }
+
+/* -------------------------------------- */
+
+/// Interrupt vector for a double fault (#DF).
+const DOUBLE_FAULT_VECTOR: usize = 8;
+
+/// Number of entries in [`IDT_ENTRIES`]. 256 covers every possible vector;
+/// only the double-fault entry is populated today.
+const IDT_ENTRY_COUNT: usize = 256;
+
+/// Present, ring-0, 32-bit task-gate type/attribute byte.
+const TASK_GATE_ATTR: u8 = 0b1000_0101;
+
+/// A single IDT descriptor. For a task gate (as used here) only `selector`
+/// and `type_attr` are meaningful; the offset fields are ignored by the CPU
+/// and must be zero.
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct IdtEntry {
+	offset_low: u16,
+	selector: u16,
+	reserved: u8,
+	type_attr: u8,
+	offset_high: u16,
+}
+
+impl IdtEntry {
+	const fn missing() -> Self {
+		IdtEntry {
+			offset_low: 0,
+			selector: 0,
+			reserved: 0,
+			type_attr: 0,
+			offset_high: 0,
+		}
+	}
+
+	const fn task_gate(tss_selector: u16) -> Self {
+		IdtEntry {
+			offset_low: 0,
+			selector: tss_selector,
+			reserved: 0,
+			type_attr: TASK_GATE_ATTR,
+			offset_high: 0,
+		}
+	}
+}
+
+#[repr(C, packed)]
+struct IdtDescriptor {
+	size: u16,
+	offset: u32,
+}
+
+/// The kernel's IDT. Only vector 8 (double fault) is populated; every other
+/// vector is left absent pending a full interrupt-handling module, so an
+/// unhandled interrupt raises a not-present fault that escalates to a double
+/// fault and ends in `double_fault_handler` rather than silently misbehaving.
+static mut IDT_ENTRIES: [IdtEntry; IDT_ENTRY_COUNT] =
+	[IdtEntry::missing(); IDT_ENTRY_COUNT];
+
+/// Installs the IDT, wiring vector 8 (double fault) to a task gate selecting
+/// [`DOUBLE_FAULT_TSS_SELECTOR`] so a double fault hardware-task-switches
+/// onto [`DOUBLE_FAULT_TSS`]'s dedicated stack instead of triple-faulting the
+/// machine.
+///
+/// Must run after the double-fault TSS descriptor has been installed in the
+/// GDT (i.e. after the `install_tss_descriptor` calls above), since the
+/// selector needs to resolve to something.
+fn idt_init() {
+	use core::mem::size_of;
+
+	// SAFETY: runs once, before interrupts are enabled, so nothing else can
+	// observe or race these writes.
+	unsafe {
+		let entries = core::ptr::addr_of_mut!(IDT_ENTRIES);
+		(*entries)[DOUBLE_FAULT_VECTOR] =
+			IdtEntry::task_gate(DOUBLE_FAULT_TSS_SELECTOR);
+
+		let descriptor = IdtDescriptor {
+			size: (size_of::<[IdtEntry; IDT_ENTRY_COUNT]>() - 1) as u16,
+			offset: entries as u32,
+		};
+
+		core::arch::asm!("lidt [{0}]", in(reg) &descriptor, options(readonly, nostack, preserves_flags));
+	}
+}
+
+/// Multiboot flag bit indicating `MultibootInfo::cmdline` is valid.
+const CMDLINE_FLAG: u32 = 1 << 2;
+
+/// Longest command line we'll copy out of bootloader-owned memory. Anything
+/// past this is silently truncated rather than risking an unbounded read.
+const MAX_CMDLINE_LEN: usize = 256;
+
+static CMDLINE: Locked<OnceCell<CmdLineData>> = Locked::new(OnceCell::new());
+
+struct CmdLineData {
+	buf: [u8; MAX_CMDLINE_LEN],
+	len: usize,
+}
+
+/// Parsed Multiboot command line.
+///
+/// The bootloader hands the kernel a pointer into its own memory, which gets
+/// reclaimed once the frame allocator comes online, so [`CmdLine::init`]
+/// copies the string out before that happens. Tokens are whitespace
+/// separated `key=value` pairs or bare flags, e.g. `log_level=debug quiet`.
+#[allow(missing_docs)]
+pub struct CmdLine;
+
+impl CmdLine {
+	/// Reads and copies the Multiboot command line out of `boot_info`, if the
+	/// bootloader provided one. Does nothing if flags bit 2 is unset.
+	///
+	/// Must run before the physical memory backing `boot_info.cmdline` can be
+	/// reclaimed, i.e. before the early memory allocator is initialized.
+	pub fn init(boot_info: &MultibootInfo) {
+		if boot_info.flags & CMDLINE_FLAG == 0 {
+			return;
+		}
+
+		let ptr = boot_info.cmdline as *const u8;
+		let mut buf = [0u8; MAX_CMDLINE_LEN];
+		let mut len = 0;
+
+		while len < MAX_CMDLINE_LEN {
+			// SAFETY: called before the early allocator reclaims bootloader
+			// memory, so the bootloader-owned command line string is still
+			// valid and NUL-terminated per the Multiboot spec.
+			let byte = unsafe { *ptr.add(len) };
+			if byte == 0 {
+				break;
+			}
+			buf[len] = byte;
+			len += 1;
+		}
+
+		CMDLINE.lock().get_or_init(|| CmdLineData { buf, len });
+	}
+
+	/// Returns the value of the `key=value` token named `key`, if present.
+	pub fn get(key: &str) -> Option<&'static str> {
+		Self::as_str()?.split_whitespace().find_map(|token| {
+			let (token_key, value) = token.split_once('=')?;
+			(token_key == key).then_some(value)
+		})
+	}
+
+	/// Returns whether the bare flag token `flag` (no `=value`) is present.
+	pub fn has(flag: &str) -> bool {
+		Self::as_str()
+			.is_some_and(|cmdline| cmdline.split_whitespace().any(|token| token == flag))
+	}
+
+	fn as_str() -> Option<&'static str> {
+		let guard = CMDLINE.lock();
+		let cmdline = guard.get()?;
+
+		// SAFETY: `CMDLINE` is written at most once, by `init`, before the
+		// rest of the kernel starts touching it; its bytes never move or
+		// change again for the remainder of the kernel's lifetime, so
+		// extending this borrow past the lock guard is sound.
+		let bytes: &'static [u8] =
+			unsafe { core::slice::from_raw_parts(cmdline.buf.as_ptr(), cmdline.len) };
+
+		str::from_utf8(bytes).ok()
+	}
+}
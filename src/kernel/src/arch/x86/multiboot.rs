@@ -1,5 +1,7 @@
 use crate::memory::RegionType;
 
+/* -------------------------------------- */
+
 #[allow(missing_docs)]
 #[cfg(target_arch = "x86")]
 #[repr(C, packed)]
@@ -103,3 +105,122 @@ pub struct MultibootInfo {
 	/// Only valid if flags[10] is set.
 	apm_table: u32,
 }
+
+/* -------------------------------------- */
+
+/// Multiboot flag bit indicating `mods_count` / `mods_addr` are valid.
+const MODS_FLAG: u32 = 1 << 3;
+
+/// On-disk layout of a single Multiboot module descriptor, as pointed to by
+/// `MultibootInfo::mods_addr`.
+#[repr(C, packed)]
+struct RawMultibootModule {
+	mod_start: u32,
+	mod_end: u32,
+	string: u32,
+	reserved: u32,
+}
+
+/// A Multiboot module (e.g. an initrd image) loaded into physical memory by
+/// the bootloader before the kernel took over.
+#[allow(missing_docs)]
+pub struct Module {
+	pub name: &'static str,
+	pub data: &'static [u8],
+}
+
+impl Module {
+	/// Physical address range backing this module, as `[start, end)`.
+	pub fn range(&self) -> (usize, usize) {
+		let start = self.data.as_ptr() as usize;
+		(start, start + self.data.len())
+	}
+}
+
+/// Iterates the Multiboot modules described by `boot_info`, validating each
+/// one's address range against the memory regions recorded in
+/// [`G_SEGMENTS`] before exposing it.
+///
+/// Yields nothing if flags bit 3 is unset. A module whose range falls
+/// outside every known-available region is skipped rather than trusted,
+/// since it would indicate a corrupt or malformed descriptor.
+pub fn modules(boot_info: &MultibootInfo) -> impl Iterator<Item = Module> {
+	let count = if boot_info.flags & MODS_FLAG != 0 {
+		boot_info.mods_count
+	} else {
+		0
+	};
+	let base = boot_info.mods_addr as *const RawMultibootModule;
+
+	(0..count).filter_map(move |i| {
+		// SAFETY: `i < count` and `count` only comes from a valid Multiboot
+		// info structure, so this stays within the bootloader's module array.
+		let raw = unsafe { &*base.add(i as usize) };
+
+		if raw.mod_end <= raw.mod_start
+			|| !range_is_available(raw.mod_start as u64, raw.mod_end as u64)
+		{
+			return None;
+		}
+
+		let name = read_c_str(raw.string as *const u8).unwrap_or("");
+		// SAFETY: the range was just checked against the detected memory
+		// map, and modules are never reclaimed by the frame allocator (see
+		// `memory_init`'s reservation pass), so this slice stays valid.
+		let data = unsafe {
+			core::slice::from_raw_parts(
+				raw.mod_start as *const u8,
+				(raw.mod_end - raw.mod_start) as usize,
+			)
+		};
+
+		Some(Module { name, data })
+	})
+}
+
+/// Returns whether `[start, end)` falls entirely within a region the
+/// bootloader reported as present memory.
+fn range_is_available(start: u64, end: u64) -> bool {
+	G_SEGMENTS.lock().iter().any(|region| {
+		let region_start = region.base() as u64;
+		let region_end = region_start + region.size() as u64;
+
+		region_start <= start && end <= region_end
+	})
+}
+
+/// Longest module name we'll read. A malformed descriptor's `string` pointer
+/// can't be trusted to ever hit a NUL, so the scan is capped regardless of
+/// what it finds.
+const MAX_MODULE_NAME_LEN: usize = 256;
+
+/// Reads a NUL-terminated string at `ptr`, returning `None` for a null
+/// pointer, a pointer outside the detected memory map, invalid UTF-8, or a
+/// string that isn't NUL-terminated within [`MAX_MODULE_NAME_LEN`] bytes.
+fn read_c_str(ptr: *const u8) -> Option<&'static str> {
+	if ptr.is_null() || !range_is_available(ptr as u64, ptr as u64 + 1) {
+		return None;
+	}
+
+	let mut len = 0;
+	let mut terminated = false;
+
+	while len < MAX_MODULE_NAME_LEN {
+		// SAFETY: `ptr` was just checked against the detected memory map,
+		// and the scan never reads past `MAX_MODULE_NAME_LEN` bytes from it,
+		// so this stays within memory the bootloader reported as available.
+		let byte = unsafe { *ptr.add(len) };
+		if byte == 0 {
+			terminated = true;
+			break;
+		}
+		len += 1;
+	}
+
+	if !terminated {
+		return None;
+	}
+
+	let bytes = unsafe { core::slice::from_raw_parts(ptr, len) };
+	core::str::from_utf8(bytes).ok()
+}
@@ -69,6 +69,7 @@ pub mod tty;
 use crate::{arch::x86::multiboot::G_SEGMENTS, sync::Mutex};
 use alloc::{boxed::Box, format};
 use arch::x86::{
+	boot::CmdLine,
 	cpu::halt,
 	multiboot::{
 		get_biggest_available_segment_index, get_memory_region, MultibootInfo,
@@ -133,6 +134,8 @@ pub extern "C" fn kernel_main(
     );
 	}
 
+	CmdLine::init(boot_info);
+
 	Logger::init(
 		"Memory Management",
 		Some("Starting memory subsystem initialization"),
@@ -1,13 +1,13 @@
 //! Defines the kernel's global memory allocator instance.
 
 use super::{
-	buddy::BuddyAllocator, memblock::MemBlockAllocator, slab::SlabCache,
+	buddy::BuddyAllocator, memblock::MemBlockAllocator, paging, slab::SlabCache,
 	NodePoolAllocator,
 };
 use crate::{
 	arch::x86::multiboot::{
-		get_biggest_available_segment_index, get_memory_region, MultibootInfo,
-		G_SEGMENTS,
+		get_biggest_available_segment_index, get_memory_region, modules,
+		MultibootInfo, G_SEGMENTS,
 	},
 	collections::linked_list::Node,
 	log_debug, log_info,
@@ -25,6 +25,97 @@ const SLAB_CACHE_COUNT: usize = 8;
 const CACHE_SIZES: [usize; SLAB_CACHE_COUNT] =
 	[8, 16, 32, 64, 128, 256, 512, 1024];
 
+/// Upper bound on concurrently outstanding buddy-backed heap allocations.
+/// Generous enough for the rare large-allocation path without needing a
+/// dynamic structure inside the allocator that would itself have to
+/// allocate.
+const MAX_BUDDY_ALLOCATIONS: usize = 64;
+
+/// Tracks a single heap allocation that was upgraded to the buddy allocator,
+/// so `dealloc` can tell the two backends apart without trusting
+/// `layout.size()` alone (alignment can force an upgrade even when the size
+/// would otherwise fit a slab).
+#[derive(Clone, Copy)]
+struct BuddyAllocation {
+	base: usize,
+	order: usize,
+}
+
+static BUDDY_ALLOCATIONS: Locked<[Option<BuddyAllocation>; MAX_BUDDY_ALLOCATIONS]> =
+	Locked::new([None; MAX_BUDDY_ALLOCATIONS]);
+
+/// Records that `base` was handed out by the buddy allocator at the given
+/// `order`. Returns `false` if the registry is full, in which case the
+/// caller must give the memory back rather than leak an untracked pointer.
+fn record_buddy_allocation(base: usize, order: usize) -> bool {
+	let mut allocations = BUDDY_ALLOCATIONS.lock();
+
+	match allocations.iter_mut().find(|slot| slot.is_none()) {
+		Some(slot) => {
+			*slot = Some(BuddyAllocation { base, order });
+			true
+		}
+		None => false,
+	}
+}
+
+/// Removes and returns the order a buddy-owned pointer was allocated with, if
+/// `base` was previously recorded by [`record_buddy_allocation`].
+fn take_buddy_allocation(base: usize) -> Option<usize> {
+	let mut allocations = BUDDY_ALLOCATIONS.lock();
+
+	let slot = allocations
+		.iter_mut()
+		.find(|slot| matches!(slot, Some(allocation) if allocation.base == base))?;
+
+	slot.take().map(|allocation| allocation.order)
+}
+
+/// Finds the smallest slab cache able to serve `layout`, or `None` if the
+/// size exceeds every cache or the requested alignment is stricter than that
+/// cache's block size can guarantee.
+fn slab_index_for(layout: Layout) -> Option<usize> {
+	CACHE_SIZES
+		.iter()
+		.position(|&cache_size| cache_size >= layout.size() && cache_size >= layout.align())
+}
+
+/// Rounds `layout` up to a page count and returns the buddy order (i.e.
+/// `log2` of that page count) needed to satisfy it.
+fn layout_to_buddy_order(layout: Layout) -> usize {
+	let size = layout.size().max(layout.align());
+	let pages = size.div_ceil(PAGE_SIZE).max(1);
+
+	pages.next_power_of_two().trailing_zeros() as usize
+}
+
+/// Allocates `layout` from [`BUDDY_PAGE_ALLOCATOR`], recording the resulting
+/// range so `dealloc` can recognise it later.
+fn alloc_from_buddy(layout: Layout) -> *mut u8 {
+	let order = layout_to_buddy_order(layout);
+
+	let addr = match BUDDY_PAGE_ALLOCATOR.lock().get_mut() {
+		Some(buddy) => buddy.alloc(order),
+		None => return ptr::null_mut(),
+	};
+
+	let addr = match addr {
+		Some(addr) => addr,
+		None => return ptr::null_mut(),
+	};
+
+	if !record_buddy_allocation(addr.as_usize(), order) {
+		// Couldn't track it, so give it straight back rather than hand out a
+		// pointer `dealloc` would never be able to place.
+		if let Some(buddy) = BUDDY_PAGE_ALLOCATOR.lock().get_mut() {
+			buddy.dealloc(addr, order);
+		}
+		return ptr::null_mut();
+	}
+
+	addr.as_usize() as *mut u8
+}
+
 // 1. Define static for the EARLY allocator (MemBlock) NO #[global_allocator]
 //    attribute here!
 #[allow(missing_docs)]
@@ -61,11 +152,11 @@ unsafe impl GlobalAlloc for Locked<KernelAllocator> {
 			return ptr::null_mut();
 		}
 
-		// TODO: If there is no cache Buddy Allocator should take over
-		let index = CACHE_SIZES
-			.iter()
-			.position(|&cache_size| cache_size >= layout.size())
-			.expect("dealloc: No suitable cache found for size {}");
+		let Some(index) = slab_index_for(layout) else {
+			// Bigger than our largest cache, or an alignment no cache can
+			// promise: the buddy allocator takes over.
+			return alloc_from_buddy(layout);
+		};
 
 		match SLAB_CACHES.lock().get_mut() {
 			Some(caches) => {
@@ -82,10 +173,14 @@ unsafe impl GlobalAlloc for Locked<KernelAllocator> {
 	#[allow(clippy::implicit_return)]
 	#[allow(clippy::expect_used)]
 	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-		// TODO: If there is no cache Buddy Allocator should take over
-		let index = CACHE_SIZES
-			.iter()
-			.position(|&cache_size| cache_size >= layout.size())
+		if let Some(order) = take_buddy_allocation(ptr as usize) {
+			if let Some(buddy) = BUDDY_PAGE_ALLOCATOR.lock().get_mut() {
+				unsafe { buddy.dealloc((ptr as usize).into(), order) };
+			}
+			return;
+		}
+
+		let index = slab_index_for(layout)
 			.expect("dealloc: No suitable cache found for size {}");
 
 		match SLAB_CACHES.lock().get_mut() {
@@ -130,6 +225,19 @@ pub fn memory_init(boot_info: &MultibootInfo) {
 	}
 	log_debug!("Initialized Memblock",);
 
+	{
+		let mut memblock = EARLY_PHYSICAL_ALLOCATOR.lock();
+		let memblock = memblock
+			.get_mut()
+			.expect("Failed to get memblock to reserve module ranges");
+
+		for module in modules(boot_info) {
+			let (start, end) = module.range();
+			memblock.reserve(start.into(), (end - start).into());
+		}
+	}
+	log_debug!("Reserved Multiboot module ranges",);
+
 	let index =
 		get_biggest_available_segment_index().expect("No segment available");
 
@@ -184,9 +292,29 @@ pub fn memory_init(boot_info: &MultibootInfo) {
 
 	log_debug!("Initialized Buddy Page Allocator",);
 
-	SLAB_CACHES
-		.lock()
-		.get_or_init(|| CACHE_SIZES.map(|size| SlabCache::new(size, 0)));
+	paging::init_heap(paging::HEAP_START, paging::HEAP_SIZE)
+		.expect("Failed to map kernel heap into virtual memory");
+	log_debug!("Mapped kernel heap virtual window",);
+
+	// Give every slab cache an equal, non-overlapping slice of the mapped
+	// heap window instead of letting them fend for themselves: this is the
+	// range `init_heap` just backed with real frames, so the caches must live
+	// on top of it rather than wherever they'd otherwise land. `region_size`
+	// is passed to `SlabCache::new` as a hard capacity, not just a starting
+	// point, so a cache under load grows its own free list rather than ever
+	// writing into the next cache's base.
+	let heap_start = paging::HEAP_START.as_usize();
+	let region_size = paging::HEAP_SIZE / SLAB_CACHE_COUNT;
+
+	SLAB_CACHES.lock().get_or_init(|| {
+		let mut next_base = heap_start;
+
+		CACHE_SIZES.map(|size| {
+			let cache = SlabCache::new(size, next_base, region_size);
+			next_base += region_size;
+			cache
+		})
+	});
 
 	log_debug!("Initialized Slab Caches",);
 
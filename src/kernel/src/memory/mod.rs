@@ -0,0 +1,68 @@
+//! Kernel memory management: physical frame allocators, the slab/buddy
+//! backed global heap allocator, and virtual-memory mapping.
+
+pub mod allocator;
+pub mod buddy;
+pub mod memblock;
+pub mod node_pool;
+pub mod paging;
+pub mod slab;
+
+pub use node_pool::NodePoolAllocator;
+
+/// Size, in bytes, of a single page on this architecture.
+pub const PAGE_SIZE: usize = 4096;
+
+/// A physical memory address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PhysAddr(usize);
+
+impl PhysAddr {
+	/// Wraps a raw physical address.
+	pub const fn new(addr: usize) -> Self {
+		PhysAddr(addr)
+	}
+
+	/// Returns the address as a plain `usize`.
+	pub const fn as_usize(&self) -> usize {
+		self.0
+	}
+}
+
+impl From<usize> for PhysAddr {
+	fn from(addr: usize) -> Self {
+		PhysAddr(addr)
+	}
+}
+
+/// A virtual memory address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VirtAddr(usize);
+
+impl VirtAddr {
+	/// Wraps a raw virtual address.
+	pub const fn new(addr: usize) -> Self {
+		VirtAddr(addr)
+	}
+
+	/// Returns the address as a plain `usize`.
+	pub const fn as_usize(&self) -> usize {
+		self.0
+	}
+}
+
+impl From<usize> for VirtAddr {
+	fn from(addr: usize) -> Self {
+		VirtAddr(addr)
+	}
+}
+
+/// Classification of a Multiboot memory-map entry (`MultibootMmapEntry::entry_type`).
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionType {
+	/// Memory usable by the kernel.
+	Available = 1,
+	/// Memory reserved by the platform/firmware; must not be allocated from.
+	Reserved = 2,
+}
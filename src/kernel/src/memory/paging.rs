@@ -0,0 +1,197 @@
+//! Virtual-memory mapping for the kernel heap.
+//!
+//! Before this module existed, `memory_init` handed the slab caches raw
+//! physical addresses straight out of [`BuddyAllocator`], so the heap was
+//! identity-mapped physical memory with no guard pages and no separation
+//! from the frame allocator. [`init_heap`] instead reserves a fixed virtual
+//! window and backs it page-by-page with frames pulled from
+//! [`BUDDY_PAGE_ALLOCATOR`], giving the heap a contiguous virtual layout
+//! independent of physical fragmentation.
+//!
+//! This targets the 32-bit, non-PAE page table format: a single page
+//! directory with 1024 entries, each pointing at a page table with 1024
+//! 4 KiB page entries. The active page directory is reached through a
+//! recursive mapping: the last page directory entry points back at the
+//! directory itself, which is the usual trick for editing page tables
+//! without a separate "physical memory is always mapped somewhere" window.
+
+use super::{allocator::BUDDY_PAGE_ALLOCATOR, PhysAddr, VirtAddr, PAGE_SIZE};
+
+/// Number of entries in a page directory or page table on this architecture.
+const ENTRIES_PER_TABLE: usize = 1024;
+
+/// Page directory entry index reserved for the recursive mapping.
+const RECURSIVE_INDEX: usize = 1023;
+
+/// Virtual address of the active page directory, reached through the
+/// recursive entry.
+const PAGE_DIRECTORY_ADDR: usize = 0xFFFF_F000;
+
+/// Base virtual address of the active page tables, reached through the
+/// recursive entry (page table `i` lives at `PAGE_TABLES_ADDR + i * 0x1000`).
+const PAGE_TABLES_ADDR: usize = 0xFFC0_0000;
+
+/// Present bit.
+const FLAG_PRESENT: u32 = 1 << 0;
+/// Read/write bit; when clear the page or table is read-only.
+const FLAG_WRITABLE: u32 = 1 << 1;
+
+/// Fixed virtual address window reserved for the kernel heap.
+pub const HEAP_START: VirtAddr = VirtAddr::new(0xD000_0000);
+
+/// Size, in bytes, of the kernel heap window.
+pub const HEAP_SIZE: usize = 16 * 1024 * 1024;
+
+/// Errors that can occur while mapping a range of virtual memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapToError {
+	/// The buddy allocator had no physical frame left to back this page.
+	FrameAllocationFailed,
+	/// The requested virtual page is already mapped to a frame.
+	PageAlreadyMapped,
+}
+
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+struct PageTableEntry(u32);
+
+impl PageTableEntry {
+	const fn empty() -> Self {
+		PageTableEntry(0)
+	}
+
+	fn is_present(&self) -> bool {
+		self.0 & FLAG_PRESENT != 0
+	}
+
+	fn set(&mut self, frame: PhysAddr, flags: u32) {
+		self.0 = (frame.as_usize() as u32 & !0xFFF) | flags;
+	}
+}
+
+/// Returns the active page directory through the recursive mapping.
+///
+/// # Safety
+/// The recursive entry must already be installed and paging must be enabled,
+/// which is true for the whole time the kernel runs past early boot.
+unsafe fn page_directory() -> &'static mut [PageTableEntry; ENTRIES_PER_TABLE] {
+	unsafe { &mut *(PAGE_DIRECTORY_ADDR as *mut [PageTableEntry; ENTRIES_PER_TABLE]) }
+}
+
+/// Returns the page table for directory index `index` through the recursive
+/// mapping.
+///
+/// # Safety
+/// `index`'s page directory entry must be present, and the same invariants
+/// as [`page_directory`] apply.
+unsafe fn page_table(index: usize) -> &'static mut [PageTableEntry; ENTRIES_PER_TABLE] {
+	unsafe {
+		&mut *((PAGE_TABLES_ADDR + index * PAGE_SIZE)
+			as *mut [PageTableEntry; ENTRIES_PER_TABLE])
+	}
+}
+
+/// Invalidates the TLB entry for `addr` so a freshly installed mapping is
+/// visible immediately.
+fn flush_tlb(addr: usize) {
+	unsafe {
+		core::arch::asm!("invlpg [{0}]", in(reg) addr, options(nostack, preserves_flags));
+	}
+}
+
+/// Installs the recursive mapping (page directory entry [`RECURSIVE_INDEX`]
+/// pointing at the page directory itself) if it isn't already present.
+///
+/// `page_directory`/`page_table` rely on this entry to reach the active page
+/// tables; without it the first access through either would dereference an
+/// unmapped address and fault. Physical memory is still identity-mapped by
+/// the time `init_heap` runs (this module is what first carves a
+/// non-identity region out of it), so the page directory's own physical
+/// address - read straight out of `cr3` - doubles as a valid virtual address
+/// we can write the self-reference through directly.
+fn ensure_recursive_mapping() {
+	let mut cr3: u32;
+	unsafe {
+		core::arch::asm!("mov {0}, cr3", out(reg) cr3, options(nomem, nostack, preserves_flags));
+	}
+
+	// SAFETY: `cr3` holds the physical base of the active page directory,
+	// which is identity-mapped at this point in boot, so treating it as a
+	// directly dereferenceable pointer is valid.
+	let directory =
+		unsafe { &mut *(cr3 as usize as *mut [PageTableEntry; ENTRIES_PER_TABLE]) };
+
+	if !directory[RECURSIVE_INDEX].is_present() {
+		directory[RECURSIVE_INDEX].set(PhysAddr::new(cr3 as usize), FLAG_PRESENT | FLAG_WRITABLE);
+		flush_tlb(PAGE_DIRECTORY_ADDR);
+	}
+}
+
+/// Allocates a single physical frame from the buddy allocator.
+fn allocate_frame() -> Result<PhysAddr, MapToError> {
+	BUDDY_PAGE_ALLOCATOR
+		.lock()
+		.get_mut()
+		.and_then(|buddy| buddy.alloc(0))
+		.ok_or(MapToError::FrameAllocationFailed)
+}
+
+/// Maps a single virtual page, allocating and installing an intermediate
+/// page table on demand.
+fn map_page(page: usize) -> Result<(), MapToError> {
+	let dir_index = page / ENTRIES_PER_TABLE;
+	let table_index = page % ENTRIES_PER_TABLE;
+
+	// SAFETY: paging is active for the kernel's entire lifetime past early
+	// boot, and `init_heap` only ever runs after that point.
+	let directory = unsafe { page_directory() };
+
+	if !directory[dir_index].is_present() {
+		let frame = allocate_frame()?;
+		directory[dir_index].set(frame, FLAG_PRESENT | FLAG_WRITABLE);
+
+		// The table's virtual window now resolves to a fresh (non-zeroed)
+		// frame; invalidate and zero it out before trusting any entry in it.
+		flush_tlb(PAGE_TABLES_ADDR + dir_index * PAGE_SIZE);
+		let table = unsafe { page_table(dir_index) };
+		for entry in table.iter_mut() {
+			*entry = PageTableEntry::empty();
+		}
+	}
+
+	let table = unsafe { page_table(dir_index) };
+	if table[table_index].is_present() {
+		return Err(MapToError::PageAlreadyMapped);
+	}
+
+	let frame = allocate_frame()?;
+	table[table_index].set(frame, FLAG_PRESENT | FLAG_WRITABLE);
+	flush_tlb(page * PAGE_SIZE);
+
+	Ok(())
+}
+
+/// Maps `size` bytes starting at `start` into the kernel's virtual address
+/// space, pulling physical frames from the buddy allocator and installing
+/// present+writable page-table entries for each one.
+///
+/// Intended to be called once from `memory_init` with [`HEAP_START`] /
+/// [`HEAP_SIZE`] before the slab caches are initialized on top of the
+/// resulting range.
+///
+/// # Errors
+/// Returns [`MapToError::FrameAllocationFailed`] if the buddy allocator runs
+/// out of frames partway through, or [`MapToError::PageAlreadyMapped`] if a
+/// page in the range was already mapped.
+pub fn init_heap(start: VirtAddr, size: usize) -> Result<(), MapToError> {
+	ensure_recursive_mapping();
+
+	let start_page = start.as_usize() / PAGE_SIZE;
+	let page_count = size.div_ceil(PAGE_SIZE);
+
+	for page in start_page..start_page + page_count {
+		map_page(page)?;
+	}
+
+	Ok(())
+}
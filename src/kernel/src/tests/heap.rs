@@ -0,0 +1,78 @@
+//! Heap stress and validation tests for the global allocator.
+//!
+//! These run through `GlobalAlloc for Locked<KernelAllocator>` exactly as the
+//! booted kernel does, so a regression in the slab/buddy split shows up here
+//! instead of as a mid-boot panic. Compiled in under the crate's
+//! `custom_test_frameworks` harness (`test_main`), not as a standalone test
+//! binary. Each test exits QEMU with its own failure code on mismatch rather
+//! than asserting, so a CI log can tell which stage regressed without
+//! parsing a panic message.
+
+use alloc::{boxed::Box, vec::Vec};
+
+use super::{exit_qemu, QemuExitCode};
+
+/// A `Box` allocates, can be read back, and frees cleanly.
+#[test_case]
+fn box_alloc_and_read_back() {
+	let value = Box::new(0x1234_5678u32);
+
+	if *value != 0x1234_5678 {
+		exit_qemu(QemuExitCode::HeapBoxMismatch);
+	}
+}
+
+/// Growing a `Vec` past several slab size classes (8, 16, 32, ... bytes of
+/// `u64` elements) forces repeated reallocation without corrupting existing
+/// elements.
+#[test_case]
+fn vec_grows_across_slab_size_classes() {
+	let mut values: Vec<u64> = Vec::new();
+
+	for i in 0..4096u64 {
+		values.push(i);
+	}
+
+	if values.len() != 4096 {
+		exit_qemu(QemuExitCode::HeapVecCorrupted);
+	}
+
+	for (i, &value) in values.iter().enumerate() {
+		if value != i as u64 {
+			exit_qemu(QemuExitCode::HeapVecCorrupted);
+		}
+	}
+}
+
+/// The size-8 cache's region holds `HEAP_SIZE / SLAB_CACHE_COUNT / 8` objects
+/// (2 MiB / 8 bytes = 262,144 on this kernel's 16 MiB heap / 8 caches split).
+/// Looping well past that would exhaust a bump allocator that never reclaims,
+/// so running it to completion only proves reuse, not merely that the heap
+/// is big enough to absorb one pass.
+const SLAB_REUSE_ITERATIONS: u32 = 1_000_000;
+
+/// Many more sequential small allocations and frees than the size-8 cache has
+/// room for at once must not exhaust the heap: each iteration's `Box` is
+/// dropped before the next is allocated, so this only passes if freed slab
+/// objects are actually reused rather than bump-allocated forever.
+#[test_case]
+fn many_small_allocations_reuse_freed_slab_objects() {
+	for i in 0..SLAB_REUSE_ITERATIONS {
+		let boxed = Box::new(i);
+
+		if *boxed != i {
+			exit_qemu(QemuExitCode::HeapSmallAllocCorrupted);
+		}
+	}
+}
+
+/// An allocation bigger than the largest slab cache (1024 bytes) must be
+/// served by the buddy-allocator fallback instead of panicking.
+#[test_case]
+fn oversized_allocation_falls_back_to_buddy_allocator() {
+	let big = Box::new([0xAAu8; 4096]);
+
+	if big.len() != 4096 || !big.iter().all(|&byte| byte == 0xAA) {
+		exit_qemu(QemuExitCode::HeapBuddyFallbackCorrupted);
+	}
+}
@@ -0,0 +1,58 @@
+//! Custom test-framework harness.
+//!
+//! Runs every `#[test_case]` function registered under this module and
+//! reports the result by writing to the `isa-debug-exit` I/O port, which
+//! QEMU maps to its own process exit code. Individual tests exit with their
+//! own failure code instead of panicking, so a regression is identifiable
+//! from the exit status alone rather than needing to parse serial output.
+
+pub mod heap;
+
+/// Exit codes written to the `isa-debug-exit` port (`0xf4`). QEMU reports
+/// `(code << 1) | 1` as its process exit status.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QemuExitCode {
+	/// Every test passed.
+	Success = 0x10,
+	/// A `Box` allocation didn't read back the value it was given.
+	HeapBoxMismatch = 0x20,
+	/// A `Vec` grown across several slab size classes lost or corrupted an
+	/// element.
+	HeapVecCorrupted = 0x21,
+	/// A sequential small-allocation loop produced a value that didn't match
+	/// what was written, implying a freed slab object wasn't reused cleanly.
+	HeapSmallAllocCorrupted = 0x22,
+	/// An allocation above the largest slab cache didn't come back from the
+	/// buddy-allocator fallback intact.
+	HeapBuddyFallbackCorrupted = 0x23,
+}
+
+const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+/// Writes `code` to the `isa-debug-exit` port, which halts the VM with a
+/// status QEMU derives from `code`. Never returns in practice; the trailing
+/// loop only matters if QEMU's debug-exit device isn't present.
+pub fn exit_qemu(code: QemuExitCode) -> ! {
+	unsafe {
+		core::arch::asm!(
+			"out dx, eax",
+			in("dx") ISA_DEBUG_EXIT_PORT,
+			in("eax") code as u32,
+			options(nomem, nostack, preserves_flags),
+		);
+	}
+
+	loop {}
+}
+
+/// Entry point wired up via `#![test_runner(crate::tests::test_runner)]`.
+/// Runs every test, then exits QEMU with [`QemuExitCode::Success`] — a test
+/// that fails is expected to have already exited with a more specific code.
+pub fn test_runner(tests: &[&dyn Fn()]) {
+	for test in tests {
+		test();
+	}
+
+	exit_qemu(QemuExitCode::Success);
+}